@@ -21,16 +21,17 @@
 //! float)`. This keeps the runtime query execution code much simpler.
 
 use std::collections::HashMap;
+use std::convert::TryFrom;
 
-use arrow::datatypes::Schema;
+use arrow::datatypes::{DataType, Schema};
 
 use crate::error::{ExecutionError, Result};
 use crate::execution::physical_plan::udf::ScalarFunction;
 use crate::logicalplan::LogicalPlan;
-use crate::logicalplan::{Expr, LogicalPlanBuilder};
+use crate::logicalplan::{Column, Expr, Operator, ScalarValue};
 use crate::optimizer::optimizer::OptimizerRule;
 use crate::optimizer::utils;
-use utils::optimize_explain;
+use utils::{optimize_explain, resolve_column, ExprSchemable};
 
 /// Implementation of type coercion optimizer rule
 pub struct TypeCoercionRule<'a> {
@@ -44,28 +45,81 @@ impl<'a> TypeCoercionRule<'a> {
         Self { scalar_functions }
     }
 
-    /// Rewrite an expression list to include explicit CAST operations when required
-    fn rewrite_expr_list(&self, expr: &[Expr], schema: &Schema) -> Result<Vec<Expr>> {
-        Ok(expr
+    /// Rewrite an expression to include explicit CAST operations when required.
+    ///
+    /// Recurses into every child expression via `optimizer::utils`'s generic
+    /// traversal helpers rather than hand-matching each `Expr` variant, so a
+    /// coercion is never silently skipped because a new variant's children
+    /// weren't visited (as used to happen for e.g. `Not`/`Sort`).
+    fn rewrite_expr(&self, expr: &Expr, schema: &Schema) -> Result<Expr> {
+        let children = utils::expr_sub_expressions(expr)?
             .iter()
             .map(|e| self.rewrite_expr(e, schema))
-            .collect::<Result<Vec<_>>>()?)
+            .collect::<Result<Vec<_>>>()?;
+        let expr = utils::rewrite_expression(expr, &children)?;
+        self.coerce(&expr, schema)
     }
 
-    /// Rewrite an expression to include explicit CAST operations when required
-    fn rewrite_expr(&self, expr: &Expr, schema: &Schema) -> Result<Expr> {
+    /// Apply the type-coercion transform to a single expression node whose
+    /// children have already been recursively coerced by `rewrite_expr`.
+    fn coerce(&self, expr: &Expr, schema: &Schema) -> Result<Expr> {
         match expr {
             Expr::BinaryExpr { left, op, right } => {
-                let left = self.rewrite_expr(left, schema)?;
-                let right = self.rewrite_expr(right, schema)?;
-                let left_type = left.get_type(schema)?;
-                let right_type = right.get_type(schema)?;
-                if left_type == right_type {
-                    Ok(Expr::BinaryExpr {
+                // `And`/`Or` don't have a supertype in the numeric sense --
+                // both sides must simply be (coerced to) Boolean.
+                if matches!(op, Operator::And | Operator::Or) {
+                    return Ok(Expr::BinaryExpr {
+                        left: Box::new(coerce_to_boolean(left, schema)?),
+                        op: op.clone(),
+                        right: Box::new(coerce_to_boolean(right, schema)?),
+                    });
+                }
+
+                // A bare `NULL` literal has no type of its own to compute a
+                // supertype from, so adopt whatever type the other side has.
+                if let Some(left) = coerce_null_literal(left, right, schema)? {
+                    return Ok(Expr::BinaryExpr {
                         left: Box::new(left),
                         op: op.clone(),
+                        right: right.clone(),
+                    });
+                }
+                if let Some(right) = coerce_null_literal(right, left, schema)? {
+                    return Ok(Expr::BinaryExpr {
+                        left: left.clone(),
+                        op: op.clone(),
                         right: Box::new(right),
-                    })
+                    });
+                }
+
+                // For comparisons, prefer folding a literal operand into the
+                // column's type over casting the column: casting the column
+                // defeats predicate push-down and statistics-based pruning.
+                // Note the *result* of a comparison is always Boolean
+                // regardless of the (now unified) operand type -- that's
+                // encoded in `Expr::get_type`'s handling of comparison
+                // operators, not here.
+                if is_comparison_operator(op) {
+                    if let Some(right) = coerce_literal_to_column_type(left, right, schema)? {
+                        return Ok(Expr::BinaryExpr {
+                            left: left.clone(),
+                            op: op.clone(),
+                            right: Box::new(right),
+                        });
+                    }
+                    if let Some(left) = coerce_literal_to_column_type(right, left, schema)? {
+                        return Ok(Expr::BinaryExpr {
+                            left: Box::new(left),
+                            op: op.clone(),
+                            right: right.clone(),
+                        });
+                    }
+                }
+
+                let left_type = left.get_type(schema)?;
+                let right_type = right.get_type(schema)?;
+                if left_type == right_type {
+                    Ok(expr.clone())
                 } else {
                     let super_type = utils::get_supertype(&left_type, &right_type)?;
                     Ok(Expr::BinaryExpr {
@@ -75,10 +129,6 @@ impl<'a> TypeCoercionRule<'a> {
                     })
                 }
             }
-            Expr::IsNull(e) => Ok(Expr::IsNull(Box::new(self.rewrite_expr(e, schema)?))),
-            Expr::IsNotNull(e) => {
-                Ok(Expr::IsNotNull(Box::new(self.rewrite_expr(e, schema)?)))
-            }
             Expr::ScalarFunction {
                 name,
                 args,
@@ -90,15 +140,14 @@ impl<'a> TypeCoercionRule<'a> {
                         let mut func_args = Vec::with_capacity(args.len());
                         for i in 0..args.len() {
                             let field = &func_meta.args[i];
-                            let expr = self.rewrite_expr(&args[i], schema)?;
-                            let actual_type = expr.get_type(schema)?;
+                            let actual_type = args[i].get_type(schema)?;
                             let required_type = field.data_type();
                             if &actual_type == required_type {
-                                func_args.push(expr)
+                                func_args.push(args[i].clone())
                             } else {
                                 let super_type =
                                     utils::get_supertype(&actual_type, required_type)?;
-                                func_args.push(expr.cast_to(&super_type, schema)?);
+                                func_args.push(args[i].cast_to(&super_type, schema)?);
                             }
                         }
 
@@ -114,83 +163,365 @@ impl<'a> TypeCoercionRule<'a> {
                     ))),
                 }
             }
-            Expr::AggregateFunction {
-                name,
-                args,
-                return_type,
-            } => Ok(Expr::AggregateFunction {
-                name: name.clone(),
-                args: args
-                    .iter()
-                    .map(|a| self.rewrite_expr(a, schema))
-                    .collect::<Result<Vec<_>>>()?,
-                return_type: return_type.clone(),
-            }),
-            Expr::Cast { .. } => Ok(expr.clone()),
-            Expr::Column(_) => Ok(expr.clone()),
-            Expr::Alias(expr, alias) => Ok(Expr::Alias(
-                Box::new(self.rewrite_expr(expr, schema)?),
-                alias.to_owned(),
-            )),
-            Expr::Literal(_) => Ok(expr.clone()),
-            Expr::Not(_) => Ok(expr.clone()),
-            Expr::Sort { .. } => Ok(expr.clone()),
+            Expr::InList {
+                expr: test_expr,
+                list,
+                negated,
+            } => {
+                // As with comparisons, prefer folding the list's literals
+                // into the test column's type over casting the column --
+                // casting the column defeats predicate push-down and
+                // statistics-based pruning.
+                if let Expr::Column(column) = test_expr.as_ref() {
+                    let items: Vec<&Expr> = list.iter().collect();
+                    if let Some(folded) = fold_literals_to_column_type(column, &items, schema)? {
+                        return Ok(Expr::InList {
+                            expr: test_expr.clone(),
+                            list: folded,
+                            negated: *negated,
+                        });
+                    }
+                }
+
+                let mut common_type = test_expr.get_type(schema)?;
+                for item in list {
+                    common_type = utils::get_supertype(&common_type, &item.get_type(schema)?)?;
+                }
+                Ok(Expr::InList {
+                    expr: Box::new(test_expr.cast_to(&common_type, schema)?),
+                    list: list
+                        .iter()
+                        .map(|item| item.cast_to(&common_type, schema))
+                        .collect::<Result<Vec<_>>>()?,
+                    negated: *negated,
+                })
+            }
+            Expr::Between {
+                expr: value,
+                negated,
+                low,
+                high,
+            } => {
+                // Same rationale as `InList` above: fold the bounds into the
+                // value column's type rather than casting the column.
+                if let Expr::Column(column) = value.as_ref() {
+                    if let Some(folded) =
+                        fold_literals_to_column_type(column, &[low.as_ref(), high.as_ref()], schema)?
+                    {
+                        let mut folded = folded.into_iter();
+                        return Ok(Expr::Between {
+                            expr: value.clone(),
+                            negated: *negated,
+                            low: Box::new(folded.next().unwrap()),
+                            high: Box::new(folded.next().unwrap()),
+                        });
+                    }
+                }
+
+                let common_type = utils::get_supertype(
+                    &utils::get_supertype(&value.get_type(schema)?, &low.get_type(schema)?)?,
+                    &high.get_type(schema)?,
+                )?;
+                Ok(Expr::Between {
+                    expr: Box::new(value.cast_to(&common_type, schema)?),
+                    negated: *negated,
+                    low: Box::new(low.cast_to(&common_type, schema)?),
+                    high: Box::new(high.cast_to(&common_type, schema)?),
+                })
+            }
+            Expr::Case {
+                expr: case_expr,
+                when_then_expr,
+                else_expr,
+            } => {
+                // THEN/ELSE results share a common result type. An empty
+                // branch list (a degenerate `CASE` with no `WHEN` at all)
+                // has no THEN to seed the supertype fold from, so fall back
+                // to the ELSE type, or leave the type unconstrained if
+                // there's neither.
+                let mut then_types = when_then_expr.iter().map(|(_, then)| then.get_type(schema));
+                let mut common_type = match then_types.next() {
+                    Some(first) => first?,
+                    None => match else_expr {
+                        Some(else_expr) => else_expr.get_type(schema)?,
+                        None => DataType::Boolean,
+                    },
+                };
+                for then_type in then_types {
+                    common_type = utils::get_supertype(&common_type, &then_type?)?;
+                }
+                if let Some(else_expr) = else_expr {
+                    common_type = utils::get_supertype(&common_type, &else_expr.get_type(schema)?)?;
+                }
+
+                // The WHEN side: a searched `CASE WHEN cond THEN ...` (no
+                // `case_expr`) requires each `cond` to be `Boolean`. A simple
+                // `CASE case_expr WHEN v THEN ...` implicitly compares
+                // `case_expr` to each `v` for equality, so they share a
+                // comparison supertype instead.
+                let new_case_expr = case_expr
+                    .as_ref()
+                    .map(|case_expr| -> Result<(Box<Expr>, DataType)> {
+                        let mut when_type = case_expr.get_type(schema)?;
+                        for (when, _) in when_then_expr {
+                            when_type = utils::get_supertype(&when_type, &when.get_type(schema)?)?;
+                        }
+                        Ok((Box::new(case_expr.cast_to(&when_type, schema)?), when_type))
+                    })
+                    .transpose()?;
+
+                Ok(Expr::Case {
+                    expr: new_case_expr.as_ref().map(|(expr, _)| expr.clone()),
+                    when_then_expr: when_then_expr
+                        .iter()
+                        .map(|(when, then)| -> Result<(Box<Expr>, Box<Expr>)> {
+                            let when = match &new_case_expr {
+                                Some((_, when_type)) => when.cast_to(when_type, schema)?,
+                                None => coerce_to_boolean(when, schema)?,
+                            };
+                            Ok((Box::new(when), Box::new(then.cast_to(&common_type, schema)?)))
+                        })
+                        .collect::<Result<Vec<_>>>()?,
+                    else_expr: else_expr
+                        .as_ref()
+                        .map(|e| e.cast_to(&common_type, schema))
+                        .transpose()?
+                        .map(Box::new),
+                })
+            }
             Expr::Wildcard { .. } => Err(ExecutionError::General(
                 "Wildcard expressions are not valid in a logical query plan".to_owned(),
             )),
-            Expr::Nested(e) => self.rewrite_expr(e, schema),
+            _ => Ok(expr.clone()),
+        }
+    }
+}
+
+/// Returns true for operators whose result is a boolean comparison of their
+/// two operands (as opposed to, say, `Plus`, whose result takes on the
+/// supertype of its operands).
+fn is_comparison_operator(op: &Operator) -> bool {
+    matches!(
+        op,
+        Operator::Eq
+            | Operator::NotEq
+            | Operator::Lt
+            | Operator::LtEq
+            | Operator::Gt
+            | Operator::GtEq
+    )
+}
+
+/// Returns `expr` unchanged if it's already `Boolean` -- the only type
+/// `And`/`Or` operands and searched-`CASE` `WHEN` predicates accept. Unlike
+/// the numeric supertype casts used elsewhere in this module, there's no
+/// general implicit cast to `Boolean`, so a non-`Boolean` operand is a clear
+/// coercion error here rather than an attempted (and likely rejected) `CAST`.
+fn coerce_to_boolean(expr: &Expr, schema: &Schema) -> Result<Expr> {
+    match expr.get_type(schema)? {
+        DataType::Boolean => Ok(expr.clone()),
+        other => Err(ExecutionError::General(format!(
+            "Cannot coerce expression of type {:?} to Boolean",
+            other
+        ))),
+    }
+}
+
+/// If `maybe_null` is a bare `NULL` literal, cast it to `other`'s type so it
+/// can participate in the rest of coercion instead of tripping up
+/// `get_supertype`, which has no meaningful supertype for an untyped `NULL`.
+/// Returns `Ok(None)` when `maybe_null` isn't a `NULL` literal.
+fn coerce_null_literal(maybe_null: &Expr, other: &Expr, schema: &Schema) -> Result<Option<Expr>> {
+    match maybe_null {
+        Expr::Literal(ScalarValue::Null) => {
+            let other_type = other.get_type(schema)?;
+            Ok(Some(maybe_null.cast_to(&other_type, schema)?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// If `column` is a bare column reference and `literal` is a literal that can
+/// be represented without loss in the column's data type, return a new
+/// literal expression of that type. Returns `Ok(None)` when `column`/`literal`
+/// aren't a column/literal pair, or when the literal can't be narrowed
+/// losslessly, in which case the caller should fall back to casting both
+/// sides to their supertype.
+///
+/// Resolves `column` via `utils::resolve_column`, which prefers an exact
+/// qualified-name match and only falls back to an unqualified lookup when
+/// it's unambiguous, erroring otherwise -- so this keeps working once
+/// `schema` covers more than one input (e.g. a join) and two inputs share a
+/// column name.
+fn coerce_literal_to_column_type(
+    column: &Expr,
+    literal: &Expr,
+    schema: &Schema,
+) -> Result<Option<Expr>> {
+    let (column, value) = match (column, literal) {
+        (Expr::Column(column), Expr::Literal(value)) => (column, value),
+        _ => return Ok(None),
+    };
+    let column_type = resolve_column(column, schema)?.data_type().clone();
+    Ok(cast_scalar_value(value, &column_type).map(Expr::Literal))
+}
+
+/// As `coerce_literal_to_column_type`, but for an `InList`/`Between`-style
+/// list of operands that must *all* be literals representable without loss
+/// in `column`'s type. Returns `Ok(None)` (rather than folding some and
+/// casting others) if any operand isn't a losslessly-narrowable literal, so
+/// the caller can fall back to its supertype-cast path for all of them,
+/// `column` included.
+fn fold_literals_to_column_type(
+    column: &Column,
+    items: &[&Expr],
+    schema: &Schema,
+) -> Result<Option<Vec<Expr>>> {
+    let column_type = resolve_column(column, schema)?.data_type().clone();
+    let mut folded = Vec::with_capacity(items.len());
+    for item in items {
+        match item {
+            Expr::Literal(value) => match cast_scalar_value(value, &column_type) {
+                Some(value) => folded.push(Expr::Literal(value)),
+                None => return Ok(None),
+            },
+            _ => return Ok(None),
+        }
+    }
+    Ok(Some(folded))
+}
+
+/// Convert `value` into an equivalent `ScalarValue` of `target_type`, or
+/// `None` if that conversion would lose information (out of range, or an
+/// incompatible kind such as string <-> numeric).
+fn cast_scalar_value(value: &ScalarValue, target_type: &DataType) -> Option<ScalarValue> {
+    if &value.get_datatype() == target_type {
+        return Some(value.clone());
+    }
+    match value {
+        ScalarValue::Int8(v) => cast_signed_literal(*v as i64, target_type),
+        ScalarValue::Int16(v) => cast_signed_literal(*v as i64, target_type),
+        ScalarValue::Int32(v) => cast_signed_literal(*v as i64, target_type),
+        ScalarValue::Int64(v) => cast_signed_literal(*v, target_type),
+        ScalarValue::UInt8(v) => cast_unsigned_literal(*v as u64, target_type),
+        ScalarValue::UInt16(v) => cast_unsigned_literal(*v as u64, target_type),
+        ScalarValue::UInt32(v) => cast_unsigned_literal(*v as u64, target_type),
+        ScalarValue::UInt64(v) => cast_unsigned_literal(*v, target_type),
+        ScalarValue::Float32(v) if *target_type == DataType::Float64 => {
+            Some(ScalarValue::Float64(*v as f64))
         }
+        _ => None,
+    }
+}
+
+fn cast_signed_literal(v: i64, target_type: &DataType) -> Option<ScalarValue> {
+    match target_type {
+        DataType::Int8 => i8::try_from(v).ok().map(ScalarValue::Int8),
+        DataType::Int16 => i16::try_from(v).ok().map(ScalarValue::Int16),
+        DataType::Int32 => i32::try_from(v).ok().map(ScalarValue::Int32),
+        DataType::Int64 => Some(ScalarValue::Int64(v)),
+        DataType::Float32 => i64_to_f32_exact(v).map(ScalarValue::Float32),
+        DataType::Float64 => i64_to_f64_exact(v).map(ScalarValue::Float64),
+        _ => None,
+    }
+}
+
+fn cast_unsigned_literal(v: u64, target_type: &DataType) -> Option<ScalarValue> {
+    match target_type {
+        DataType::UInt8 => u8::try_from(v).ok().map(ScalarValue::UInt8),
+        DataType::UInt16 => u16::try_from(v).ok().map(ScalarValue::UInt16),
+        DataType::UInt32 => u32::try_from(v).ok().map(ScalarValue::UInt32),
+        DataType::UInt64 => Some(ScalarValue::UInt64(v)),
+        DataType::Int8 => i8::try_from(v).ok().map(ScalarValue::Int8),
+        DataType::Int16 => i16::try_from(v).ok().map(ScalarValue::Int16),
+        DataType::Int32 => i32::try_from(v).ok().map(ScalarValue::Int32),
+        DataType::Int64 => i64::try_from(v).ok().map(ScalarValue::Int64),
+        DataType::Float32 => u64_to_f32_exact(v).map(ScalarValue::Float32),
+        DataType::Float64 => u64_to_f64_exact(v).map(ScalarValue::Float64),
+        _ => None,
+    }
+}
+
+/// Returns `v` as an `f32` only if that conversion round-trips exactly (i.e.
+/// doesn't lose precision), so the "representable without loss" contract of
+/// `cast_scalar_value` actually holds for the float targets too -- `v as f32`
+/// alone silently rounds once `v` exceeds `f32`'s 24-bit mantissa.
+fn i64_to_f32_exact(v: i64) -> Option<f32> {
+    let f = v as f32;
+    // compare in a wider type: `f as i64` saturates, so `i64::MAX` (which
+    // rounds up to 2^63) would otherwise look like an exact round trip
+    if f as i128 == v as i128 {
+        Some(f)
+    } else {
+        None
+    }
+}
+
+/// As `i64_to_f32_exact`, but for `f64`'s 53-bit mantissa.
+fn i64_to_f64_exact(v: i64) -> Option<f64> {
+    let f = v as f64;
+    if f as i128 == v as i128 {
+        Some(f)
+    } else {
+        None
+    }
+}
+
+/// As `i64_to_f32_exact`, for the unsigned literal kinds.
+fn u64_to_f32_exact(v: u64) -> Option<f32> {
+    let f = v as f32;
+    if f as u128 == v as u128 {
+        Some(f)
+    } else {
+        None
+    }
+}
+
+/// As `i64_to_f64_exact`, for the unsigned literal kinds.
+fn u64_to_f64_exact(v: u64) -> Option<f64> {
+    let f = v as f64;
+    if f as u128 == v as u128 {
+        Some(f)
+    } else {
+        None
     }
 }
 
 impl<'a> OptimizerRule for TypeCoercionRule<'a> {
+    /// Recurse via the generic `optimizer::utils` plan-traversal helpers
+    /// instead of hand-matching every `LogicalPlan` variant: every node's
+    /// input(s) are optimized first, then its own expressions (if any) are
+    /// coerced against the *original* input's schema, and the node is
+    /// reassembled. `Explain` is the one variant with no expressions/inputs
+    /// of its own shape to recurse through generically, so it keeps its
+    /// dedicated path.
     fn optimize(&mut self, plan: &LogicalPlan) -> Result<LogicalPlan> {
-        match plan {
-            LogicalPlan::Projection { expr, input, .. } => {
-                LogicalPlanBuilder::from(&self.optimize(input)?)
-                    .project(self.rewrite_expr_list(expr, input.schema())?)?
-                    .build()
-            }
-            LogicalPlan::Selection { expr, input, .. } => {
-                LogicalPlanBuilder::from(&self.optimize(input)?)
-                    .filter(self.rewrite_expr(expr, input.schema())?)?
-                    .build()
-            }
-            LogicalPlan::Aggregate {
-                input,
-                group_expr,
-                aggr_expr,
-                ..
-            } => LogicalPlanBuilder::from(&self.optimize(input)?)
-                .aggregate(
-                    self.rewrite_expr_list(group_expr, input.schema())?,
-                    self.rewrite_expr_list(aggr_expr, input.schema())?,
-                )?
-                .build(),
-            LogicalPlan::Limit { n, input, .. } => {
-                LogicalPlanBuilder::from(&self.optimize(input)?)
-                    .limit(*n)?
-                    .build()
-            }
-            LogicalPlan::Sort { input, expr, .. } => {
-                LogicalPlanBuilder::from(&self.optimize(input)?)
-                    .sort(self.rewrite_expr_list(expr, input.schema())?)?
-                    .build()
-            }
-            // the following rules do not have inputs and do not need to be re-written
-            LogicalPlan::TableScan { .. } => Ok(plan.clone()),
-            LogicalPlan::InMemoryScan { .. } => Ok(plan.clone()),
-            LogicalPlan::ParquetScan { .. } => Ok(plan.clone()),
-            LogicalPlan::CsvScan { .. } => Ok(plan.clone()),
-            LogicalPlan::EmptyRelation { .. } => Ok(plan.clone()),
-            LogicalPlan::CreateExternalTable { .. } => Ok(plan.clone()),
-            LogicalPlan::Explain {
-                verbose,
-                plan,
-                stringified_plans,
-                schema,
-            } => optimize_explain(self, *verbose, &*plan, stringified_plans, &*schema),
+        if let LogicalPlan::Explain {
+            verbose,
+            plan,
+            stringified_plans,
+            schema,
+        } = plan
+        {
+            return optimize_explain(self, *verbose, &*plan, stringified_plans, &*schema);
         }
+
+        let inputs = utils::inputs(plan);
+        let new_inputs = inputs
+            .iter()
+            .map(|input| self.optimize(input))
+            .collect::<Result<Vec<_>>>()?;
+
+        let expr = match inputs.first() {
+            Some(input) => utils::expressions(plan)
+                .iter()
+                .map(|e| self.rewrite_expr(e, input.schema()))
+                .collect::<Result<Vec<_>>>()?,
+            None => utils::expressions(plan),
+        };
+
+        let plan = utils::from_inputs(plan, &new_inputs)?;
+        utils::from_expressions(&plan, &expr)
     }
 
     fn name(&self) -> &str {
@@ -203,7 +534,7 @@ mod tests {
     use super::*;
     use crate::execution::context::ExecutionContext;
     use crate::execution::physical_plan::csv::CsvReadOptions;
-    use crate::logicalplan::{aggregate_expr, col, lit, Operator};
+    use crate::logicalplan::{aggregate_expr, col, lit, LogicalPlanBuilder, Operator};
     use crate::test::arrow_testdata_path;
     use arrow::datatypes::{DataType, Field, Schema};
 
@@ -231,12 +562,11 @@ mod tests {
 
         // check that the filter had a cast added
         let plan_str = format!("{:?}", plan);
-        println!("{}", plan_str);
         let expected_plan_str = "Limit: 10
   Sort: #c1
     Aggregate: groupBy=[[#c1]], aggr=[[SUM(#c2)]]
       Projection: #c1, #c2
-        Selection: #c7 Lt CAST(UInt8(5) AS Int64)";
+        Selection: #c7 Lt Int64(5)";
         assert!(plan_str.starts_with(expected_plan_str));
 
         Ok(())
@@ -319,6 +649,320 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_coerce_literal_into_comparison_column() {
+        let schema = Schema::new(vec![Field::new("c0", DataType::Int64, true)]);
+        let expr = Expr::BinaryExpr {
+            left: Box::new(col("c0")),
+            op: Operator::Lt,
+            right: Box::new(lit(5_u8)),
+        };
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+        let expr2 = rule.rewrite_expr(&expr, &schema).unwrap();
+
+        // the literal is folded into the column's type; the column is left
+        // untouched so pushdown/pruning on #c0 still applies
+        assert_eq!("#c0 Lt Int64(5)", format!("{:?}", expr2));
+    }
+
+    #[test]
+    fn test_coerce_literal_resolves_qualified_column_on_a_join_schema() {
+        // once a plan has more than one input (e.g. either side of a join),
+        // two inputs can expose a column with the same unqualified name --
+        // resolution must pick the one the qualifier actually points at.
+        let schema = Schema::new(vec![
+            Field::new("t1.id", DataType::Int32, true),
+            Field::new("t2.id", DataType::Int64, true),
+        ]);
+        let expr = Expr::BinaryExpr {
+            left: Box::new(Expr::Column(Column {
+                relation: Some("t2".to_owned()),
+                name: "id".to_owned(),
+            })),
+            op: Operator::Lt,
+            right: Box::new(lit(5_u8)),
+        };
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+        let expr2 = rule.rewrite_expr(&expr, &schema).unwrap();
+
+        // folded against t2.id (Int64), not t1.id (Int32) -- the literal
+        // becomes Int64(5) and the column is left untouched (no CAST).
+        let plan_str = format!("{:?}", expr2);
+        assert!(plan_str.contains("Int64(5)"));
+        assert!(!plan_str.contains("CAST"));
+    }
+
+    #[test]
+    fn test_coerce_literal_errors_on_ambiguous_unqualified_column() {
+        let schema = Schema::new(vec![
+            Field::new("t1.id", DataType::Int32, true),
+            Field::new("t2.id", DataType::Int64, true),
+        ]);
+        let expr = Expr::BinaryExpr {
+            left: Box::new(Expr::Column(Column {
+                relation: None,
+                name: "id".to_owned(),
+            })),
+            op: Operator::Lt,
+            right: Box::new(lit(5_u8)),
+        };
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+        let err = rule.rewrite_expr(&expr, &schema).unwrap_err();
+
+        assert!(format!("{}", err).contains("Ambiguous"));
+    }
+
+    #[test]
+    fn test_coercion_recurses_into_not() {
+        // `Not` wraps its inner expression rather than being clone-passed
+        // through untouched, so the comparison inside it still gets coerced.
+        let schema = Schema::new(vec![Field::new("c0", DataType::Int64, true)]);
+        let expr = Expr::Not(Box::new(Expr::BinaryExpr {
+            left: Box::new(col("c0")),
+            op: Operator::Lt,
+            right: Box::new(lit(5_u8)),
+        }));
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+        let expr2 = rule.rewrite_expr(&expr, &schema).unwrap();
+
+        assert_eq!("NOT #c0 Lt Int64(5)", format!("{:?}", expr2));
+    }
+
+    #[test]
+    fn test_coerce_literal_out_of_range_falls_back_to_cast() {
+        let schema = Schema::new(vec![Field::new("c0", DataType::Int8, true)]);
+        let expr = Expr::BinaryExpr {
+            left: Box::new(col("c0")),
+            op: Operator::Lt,
+            right: Box::new(lit(1000_i64)),
+        };
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+        let expr2 = rule.rewrite_expr(&expr, &schema).unwrap();
+
+        // 1000 doesn't fit in Int8, so we fall back to the supertype cast
+        assert_eq!("CAST(#c0 AS Int64) Lt Int64(1000)", format!("{:?}", expr2));
+    }
+
+    #[test]
+    fn test_coerce_and_or_to_boolean() {
+        let schema = Schema::new(vec![
+            Field::new("c0", DataType::Boolean, true),
+            Field::new("c1", DataType::Boolean, true),
+        ]);
+        let expr = Expr::BinaryExpr {
+            left: Box::new(col("c0")),
+            op: Operator::And,
+            right: Box::new(col("c1")),
+        };
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+        let expr2 = rule.rewrite_expr(&expr, &schema).unwrap();
+
+        assert_eq!("#c0 And #c1", format!("{:?}", expr2));
+    }
+
+    #[test]
+    fn test_coerce_null_literal_to_operand_type() {
+        let schema = Schema::new(vec![Field::new("c0", DataType::Int64, true)]);
+        let expr = Expr::BinaryExpr {
+            left: Box::new(col("c0")),
+            op: Operator::Eq,
+            right: Box::new(Expr::Literal(ScalarValue::Null)),
+        };
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+        let expr2 = rule.rewrite_expr(&expr, &schema).unwrap();
+
+        assert_eq!("#c0 Eq CAST(NULL AS Int64)", format!("{:?}", expr2));
+    }
+
+    #[test]
+    fn test_coerce_in_list() {
+        let schema = Schema::new(vec![Field::new("c0", DataType::Int64, true)]);
+        let expr = Expr::InList {
+            expr: Box::new(col("c0")),
+            list: vec![lit(1_u8), lit(2_i32)],
+            negated: false,
+        };
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+        let expr2 = rule.rewrite_expr(&expr, &schema).unwrap();
+
+        assert_eq!("#c0 IN ([Int64(1), Int64(2)])", format!("{:?}", expr2));
+    }
+
+    #[test]
+    fn test_coerce_between_bounds() {
+        let schema = Schema::new(vec![Field::new("c0", DataType::Int64, true)]);
+        let expr = Expr::Between {
+            expr: Box::new(col("c0")),
+            negated: false,
+            low: Box::new(lit(1_u8)),
+            high: Box::new(lit(10_i32)),
+        };
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+        let expr2 = rule.rewrite_expr(&expr, &schema).unwrap();
+
+        assert_eq!(
+            "#c0 BETWEEN Int64(1) AND Int64(10)",
+            format!("{:?}", expr2)
+        );
+    }
+
+    #[test]
+    fn test_coerce_case_branches() {
+        let schema = Schema::new(vec![Field::new("c0", DataType::Boolean, true)]);
+        let expr = Expr::Case {
+            expr: None,
+            when_then_expr: vec![(Box::new(col("c0")), Box::new(lit(1_i32)))],
+            else_expr: Some(Box::new(lit(2_i64))),
+        };
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+        let expr2 = rule.rewrite_expr(&expr, &schema).unwrap();
+
+        // THEN/ELSE results are coerced to their common supertype (Int64);
+        // unlike the column-vs-literal comparison path, a THEN/ELSE result
+        // isn't a predicate operand, so there's no push-down reason to fold
+        // the literal instead of casting it -- it's simply wrapped in a CAST.
+        assert_eq!(
+            "CASE WHEN #c0 THEN CAST(Int32(1) AS Int64) ELSE Int64(2) END",
+            format!("{:?}", expr2)
+        );
+    }
+
+    #[test]
+    fn test_coerce_case_with_no_branches_does_not_panic() {
+        // a `CASE` with no `WHEN` at all has nothing to seed a THEN
+        // supertype fold from -- make sure that's handled rather than
+        // indexing into an empty `when_then_expr`.
+        let schema = Schema::new(vec![]);
+        let expr = Expr::Case {
+            expr: None,
+            when_then_expr: vec![],
+            else_expr: Some(Box::new(lit(2_i64))),
+        };
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+        let expr2 = rule.rewrite_expr(&expr, &schema).unwrap();
+
+        assert_eq!("CASE ELSE Int64(2) END", format!("{:?}", expr2));
+    }
+
+    #[test]
+    fn test_coerce_searched_case_when_to_boolean() {
+        // a searched `CASE WHEN cond THEN ...` (no `case_expr`) requires
+        // each `cond` to already be `Boolean`, just like `And`/`Or` -- a
+        // `Boolean` predicate passes through untouched.
+        let schema = Schema::new(vec![Field::new("c0", DataType::Boolean, true)]);
+        let expr = Expr::Case {
+            expr: None,
+            when_then_expr: vec![(Box::new(col("c0")), Box::new(lit(1_i64)))],
+            else_expr: Some(Box::new(lit(2_i64))),
+        };
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+        let expr2 = rule.rewrite_expr(&expr, &schema).unwrap();
+
+        assert_eq!(
+            "CASE WHEN #c0 THEN Int64(1) ELSE Int64(2) END",
+            format!("{:?}", expr2)
+        );
+    }
+
+    #[test]
+    fn test_coerce_searched_case_when_requires_boolean() {
+        // unlike the numeric supertype casts used elsewhere in this module,
+        // there's no general implicit numeric -> Boolean cast, so a
+        // non-Boolean WHEN predicate is a coercion error rather than a CAST.
+        let schema = Schema::new(vec![Field::new("c0", DataType::Int32, true)]);
+        let expr = Expr::Case {
+            expr: None,
+            when_then_expr: vec![(Box::new(col("c0")), Box::new(lit(1_i64)))],
+            else_expr: Some(Box::new(lit(2_i64))),
+        };
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+        let err = rule.rewrite_expr(&expr, &schema).unwrap_err();
+
+        assert!(format!("{}", err).contains("Boolean"));
+    }
+
+    #[test]
+    fn test_coerce_simple_case_expr_and_when_share_supertype() {
+        // a simple `CASE case_expr WHEN v THEN ...` implicitly compares
+        // `case_expr` to each `v`, so they're coerced to a shared type
+        // rather than `case_expr` having to be `Boolean`.
+        let schema = Schema::new(vec![Field::new("c0", DataType::Int32, true)]);
+        let expr = Expr::Case {
+            expr: Some(Box::new(col("c0"))),
+            when_then_expr: vec![(Box::new(lit(1_i64)), Box::new(lit(10_i32)))],
+            else_expr: Some(Box::new(lit(20_i64))),
+        };
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+        let expr2 = rule.rewrite_expr(&expr, &schema).unwrap();
+
+        // `case_expr` (c0: Int32) and the WHEN value (Int64(1)) are coerced
+        // to their shared supertype (Int64), rather than c0 being forced to
+        // Boolean the way a searched CASE's WHEN predicates are.
+        let plan_str = format!("{:?}", expr2);
+        assert!(plan_str.contains("CAST(#c0 AS Int64)"));
+        assert!(plan_str.contains("Int64(1)"));
+        assert!(!plan_str.contains("Boolean"));
+    }
+
+    #[test]
+    fn test_coerced_cast_preserves_nullability() -> Result<()> {
+        // a cast is nullable iff the operand it wraps is, so coercing a
+        // nullable column should still report nullable afterwards, and a
+        // non-nullable one should still report non-nullable.
+        let schema = Schema::new(vec![
+            Field::new("c0", DataType::Int32, true),
+            Field::new("c1", DataType::Int64, false),
+            Field::new("c2", DataType::Int32, false),
+        ]);
+
+        let ctx = ExecutionContext::new();
+        let rule = TypeCoercionRule::new(ctx.scalar_functions());
+
+        let nullable_lhs = Expr::BinaryExpr {
+            left: Box::new(col("c0")),
+            op: Operator::Plus,
+            right: Box::new(col("c1")),
+        };
+        assert!(rule.rewrite_expr(&nullable_lhs, &schema)?.nullable(&schema)?);
+
+        let non_nullable = Expr::BinaryExpr {
+            left: Box::new(col("c2")),
+            op: Operator::Plus,
+            right: Box::new(col("c1")),
+        };
+        assert!(!rule.rewrite_expr(&non_nullable, &schema)?.nullable(&schema)?);
+
+        Ok(())
+    }
+
     fn binary_cast_test(left_type: DataType, right_type: DataType, expected: &str) {
         let schema = Schema::new(vec![
             Field::new("c0", left_type, true),
@@ -0,0 +1,692 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Collection of utility functions shared by the optimizer rules, in particular a
+//! generic traversal API over `LogicalPlan` and `Expr` so that a rule can recurse
+//! into every current (and future) variant without hand-enumerating them, plus the
+//! `ExprSchemable` trait giving every rule a single source of truth for an
+//! expression's type *and* nullability.
+
+use arrow::datatypes::{DataType, Field, Schema};
+
+use crate::error::{ExecutionError, Result};
+use crate::logicalplan::{Column, Expr, LogicalPlan, PlanType, ScalarValue, StringifiedPlan};
+use crate::optimizer::optimizer::OptimizerRule;
+
+/// Returns the top-level expressions owned by a `LogicalPlan` node (not
+/// recursing into its input(s)). Used together with `from_expressions` so an
+/// optimizer rule can rewrite a node's expressions without hand-matching every
+/// `LogicalPlan` variant.
+pub fn expressions(plan: &LogicalPlan) -> Vec<Expr> {
+    match plan {
+        LogicalPlan::Projection { expr, .. } => expr.clone(),
+        LogicalPlan::Selection { expr, .. } => vec![expr.clone()],
+        LogicalPlan::Aggregate {
+            group_expr,
+            aggr_expr,
+            ..
+        } => group_expr.iter().chain(aggr_expr.iter()).cloned().collect(),
+        LogicalPlan::Sort { expr, .. } => expr.clone(),
+        LogicalPlan::Limit { .. }
+        | LogicalPlan::TableScan { .. }
+        | LogicalPlan::InMemoryScan { .. }
+        | LogicalPlan::ParquetScan { .. }
+        | LogicalPlan::CsvScan { .. }
+        | LogicalPlan::EmptyRelation { .. }
+        | LogicalPlan::CreateExternalTable { .. }
+        | LogicalPlan::Explain { .. } => vec![],
+    }
+}
+
+/// Rebuilds `plan` with its top-level expressions replaced by `expr`, leaving
+/// its input(s) untouched. The inverse of `expressions`.
+pub fn from_expressions(plan: &LogicalPlan, expr: &[Expr]) -> Result<LogicalPlan> {
+    Ok(match plan {
+        LogicalPlan::Projection { input, schema, .. } => {
+            expect_exprs(expr, schema.fields().len(), "Projection")?;
+            LogicalPlan::Projection {
+                expr: expr.to_vec(),
+                input: input.clone(),
+                schema: schema_with_nullability(schema, expr, input.schema())?,
+            }
+        }
+        LogicalPlan::Selection { input, .. } => {
+            let expr = match expr {
+                [expr] => expr.clone(),
+                _ => {
+                    return Err(ExecutionError::General(format!(
+                        "Selection expects exactly one expression, got {}",
+                        expr.len()
+                    )))
+                }
+            };
+            LogicalPlan::Selection {
+                expr,
+                input: input.clone(),
+            }
+        }
+        LogicalPlan::Aggregate {
+            input,
+            group_expr,
+            schema,
+            ..
+        } => {
+            expect_exprs(expr, schema.fields().len(), "Aggregate")?;
+            if expr.len() < group_expr.len() {
+                return Err(ExecutionError::General(format!(
+                    "Aggregate expects at least {} (group-by) expressions, got {}",
+                    group_expr.len(),
+                    expr.len()
+                )));
+            }
+            let (group_expr, aggr_expr) = expr.split_at(group_expr.len());
+            LogicalPlan::Aggregate {
+                input: input.clone(),
+                group_expr: group_expr.to_vec(),
+                aggr_expr: aggr_expr.to_vec(),
+                schema: schema_with_nullability(schema, expr, input.schema())?,
+            }
+        }
+        LogicalPlan::Sort { input, schema, .. } => LogicalPlan::Sort {
+            expr: expr.to_vec(),
+            input: input.clone(),
+            schema: schema.clone(),
+        },
+        _ => plan.clone(),
+    })
+}
+
+/// Rebuilds `schema`'s fields with their `nullable` flag recomputed from
+/// `expr` (the node's new, post-coercion expressions) resolved against
+/// `resolve_schema` (typically the node's input schema), rather than
+/// carrying over whatever nullability the pre-coercion builder set. Used by
+/// `from_expressions` so the rewritten node's schema reports the nullability
+/// of the expressions it now holds. Field names and data types are kept from
+/// `schema` as they are.
+fn schema_with_nullability(schema: &Schema, expr: &[Expr], resolve_schema: &Schema) -> Result<Schema> {
+    let fields = schema
+        .fields()
+        .iter()
+        .zip(expr)
+        .map(|(field, e)| -> Result<Field> {
+            Ok(Field::new(
+                field.name(),
+                field.data_type().clone(),
+                e.nullable(resolve_schema)?,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Schema::new(fields))
+}
+
+/// Returns an error unless `expr` has exactly `expected` elements. Used by
+/// `from_expressions` for `Projection` and `Aggregate`, so a node whose
+/// expression count doesn't match its schema's field count is an error rather
+/// than a panic in `split_at` or a field list silently cut short by the
+/// `zip` in `schema_with_nullability`.
+fn expect_exprs(expr: &[Expr], expected: usize, variant: &str) -> Result<()> {
+    if expr.len() != expected {
+        return Err(ExecutionError::General(format!(
+            "{} expects {} expression(s), got {}",
+            variant,
+            expected,
+            expr.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Returns the input(s) of a `LogicalPlan` node, or an empty `Vec` for a leaf
+/// node (a scan, `EmptyRelation`, etc).
+pub fn inputs(plan: &LogicalPlan) -> Vec<&LogicalPlan> {
+    match plan {
+        LogicalPlan::Projection { input, .. }
+        | LogicalPlan::Selection { input, .. }
+        | LogicalPlan::Aggregate { input, .. }
+        | LogicalPlan::Limit { input, .. }
+        | LogicalPlan::Sort { input, .. } => vec![input.as_ref()],
+        LogicalPlan::TableScan { .. }
+        | LogicalPlan::InMemoryScan { .. }
+        | LogicalPlan::ParquetScan { .. }
+        | LogicalPlan::CsvScan { .. }
+        | LogicalPlan::EmptyRelation { .. }
+        | LogicalPlan::CreateExternalTable { .. }
+        | LogicalPlan::Explain { .. } => vec![],
+    }
+}
+
+/// Rebuilds `plan` with its input(s) replaced by `inputs`, leaving its own
+/// expressions untouched. The inverse of `inputs`.
+pub fn from_inputs(plan: &LogicalPlan, inputs: &[LogicalPlan]) -> Result<LogicalPlan> {
+    Ok(match plan {
+        LogicalPlan::Projection { expr, schema, .. } => LogicalPlan::Projection {
+            expr: expr.clone(),
+            input: Box::new(single_input(inputs, "Projection")?.clone()),
+            schema: schema.clone(),
+        },
+        LogicalPlan::Selection { expr, .. } => LogicalPlan::Selection {
+            expr: expr.clone(),
+            input: Box::new(single_input(inputs, "Selection")?.clone()),
+        },
+        LogicalPlan::Aggregate {
+            group_expr,
+            aggr_expr,
+            schema,
+            ..
+        } => LogicalPlan::Aggregate {
+            input: Box::new(single_input(inputs, "Aggregate")?.clone()),
+            group_expr: group_expr.clone(),
+            aggr_expr: aggr_expr.clone(),
+            schema: schema.clone(),
+        },
+        LogicalPlan::Limit { n, .. } => LogicalPlan::Limit {
+            n: *n,
+            input: Box::new(single_input(inputs, "Limit")?.clone()),
+        },
+        LogicalPlan::Sort { expr, schema, .. } => LogicalPlan::Sort {
+            expr: expr.clone(),
+            input: Box::new(single_input(inputs, "Sort")?.clone()),
+            schema: schema.clone(),
+        },
+        _ => plan.clone(),
+    })
+}
+
+/// Returns the single input at `inputs[0]`, erroring instead of panicking
+/// when `inputs` doesn't contain exactly the one input these single-input
+/// `LogicalPlan` variants expect. A variant that `from_inputs` doesn't match
+/// explicitly never reaches this check: its `_` arm returns such a node
+/// unchanged, with its original input(s).
+fn single_input<'a>(inputs: &'a [LogicalPlan], variant: &str) -> Result<&'a LogicalPlan> {
+    match inputs {
+        [input] => Ok(input),
+        _ => Err(ExecutionError::General(format!(
+            "{} expects exactly one input, got {}",
+            variant,
+            inputs.len()
+        ))),
+    }
+}
+
+/// Returns the child expressions of a single `Expr` node (not recursing
+/// further). Used together with `rewrite_expression` so a rule can recurse
+/// into every `Expr` variant uniformly instead of hand-matching each one
+/// (and risking silently skipping a new variant's children, as happened with
+/// `Not` and `Sort` before this helper existed).
+pub fn expr_sub_expressions(expr: &Expr) -> Result<Vec<Expr>> {
+    Ok(match expr {
+        Expr::BinaryExpr { left, right, .. } => vec![*left.clone(), *right.clone()],
+        Expr::IsNull(e) => vec![*e.clone()],
+        Expr::IsNotNull(e) => vec![*e.clone()],
+        Expr::Not(e) => vec![*e.clone()],
+        Expr::Nested(e) => vec![*e.clone()],
+        Expr::Cast { expr, .. } => vec![*expr.clone()],
+        Expr::Alias(expr, _) => vec![*expr.clone()],
+        Expr::Sort { expr, .. } => vec![*expr.clone()],
+        Expr::ScalarFunction { args, .. } => args.clone(),
+        Expr::AggregateFunction { args, .. } => args.clone(),
+        Expr::InList { expr, list, .. } => {
+            let mut exprs = vec![*expr.clone()];
+            exprs.extend(list.iter().cloned());
+            exprs
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } => vec![*expr.clone(), *low.clone(), *high.clone()],
+        Expr::Case {
+            expr,
+            when_then_expr,
+            else_expr,
+        } => {
+            let mut exprs: Vec<Expr> = expr.iter().map(|e| (**e).clone()).collect();
+            for (when, then) in when_then_expr {
+                exprs.push((**when).clone());
+                exprs.push((**then).clone());
+            }
+            exprs.extend(else_expr.iter().map(|e| (**e).clone()));
+            exprs
+        }
+        Expr::Column(_) | Expr::Literal(_) | Expr::Wildcard { .. } => vec![],
+    })
+}
+
+/// Rebuilds a single `Expr` node from `expressions`, the (already rewritten)
+/// result of `expr_sub_expressions`. The inverse of `expr_sub_expressions`.
+pub fn rewrite_expression(expr: &Expr, expressions: &[Expr]) -> Result<Expr> {
+    Ok(match expr {
+        Expr::BinaryExpr { op, .. } => Expr::BinaryExpr {
+            left: Box::new(expressions[0].clone()),
+            op: op.clone(),
+            right: Box::new(expressions[1].clone()),
+        },
+        Expr::IsNull(_) => Expr::IsNull(Box::new(expressions[0].clone())),
+        Expr::IsNotNull(_) => Expr::IsNotNull(Box::new(expressions[0].clone())),
+        Expr::Not(_) => Expr::Not(Box::new(expressions[0].clone())),
+        Expr::Nested(_) => Expr::Nested(Box::new(expressions[0].clone())),
+        Expr::Cast { data_type, .. } => Expr::Cast {
+            expr: Box::new(expressions[0].clone()),
+            data_type: data_type.clone(),
+        },
+        Expr::Alias(_, alias) => Expr::Alias(Box::new(expressions[0].clone()), alias.clone()),
+        Expr::Sort {
+            asc, nulls_first, ..
+        } => Expr::Sort {
+            expr: Box::new(expressions[0].clone()),
+            asc: *asc,
+            nulls_first: *nulls_first,
+        },
+        Expr::ScalarFunction {
+            name, return_type, ..
+        } => Expr::ScalarFunction {
+            name: name.clone(),
+            args: expressions.to_vec(),
+            return_type: return_type.clone(),
+        },
+        Expr::AggregateFunction {
+            name, return_type, ..
+        } => Expr::AggregateFunction {
+            name: name.clone(),
+            args: expressions.to_vec(),
+            return_type: return_type.clone(),
+        },
+        Expr::InList { negated, .. } => Expr::InList {
+            expr: Box::new(expressions[0].clone()),
+            list: expressions[1..].to_vec(),
+            negated: *negated,
+        },
+        Expr::Between { negated, .. } => Expr::Between {
+            expr: Box::new(expressions[0].clone()),
+            negated: *negated,
+            low: Box::new(expressions[1].clone()),
+            high: Box::new(expressions[2].clone()),
+        },
+        Expr::Case {
+            expr,
+            when_then_expr,
+            else_expr,
+        } => {
+            // `expressions` is laid out the same way `expr_sub_expressions`
+            // flattened it: an optional case expression, then a (when, then)
+            // pair per branch, then an optional else expression.
+            let mut i = 0;
+            let case_expr = if expr.is_some() {
+                i += 1;
+                Some(Box::new(expressions[0].clone()))
+            } else {
+                None
+            };
+            let mut branches = Vec::with_capacity(when_then_expr.len());
+            for _ in when_then_expr {
+                branches.push((
+                    Box::new(expressions[i].clone()),
+                    Box::new(expressions[i + 1].clone()),
+                ));
+                i += 2;
+            }
+            let else_expr = if else_expr.is_some() {
+                Some(Box::new(expressions[i].clone()))
+            } else {
+                None
+            };
+            Expr::Case {
+                expr: case_expr,
+                when_then_expr: branches,
+                else_expr,
+            }
+        }
+        Expr::Column(_) | Expr::Literal(_) | Expr::Wildcard { .. } => expr.clone(),
+    })
+}
+
+/// Returns the common type that both `l` and `r` are cast to, for use by
+/// rules (such as type coercion) that need to unify the operand types of a
+/// binary operator. Not every pairing is lossless: e.g. `Int32` with
+/// `Float32` gives `Float32`, and `Int64` with `Float64` gives `Float64`,
+/// both of which can round large integer values.
+pub fn get_supertype(l: &DataType, r: &DataType) -> Result<DataType> {
+    use DataType::*;
+    match (l, r) {
+        (t, u) if t == u => Ok(t.clone()),
+        (Int8, Int16) | (Int16, Int8) => Ok(Int16),
+        (Int8, Int32) | (Int32, Int8) => Ok(Int32),
+        (Int8, Int64) | (Int64, Int8) => Ok(Int64),
+        (Int16, Int32) | (Int32, Int16) => Ok(Int32),
+        (Int16, Int64) | (Int64, Int16) => Ok(Int64),
+        (Int32, Int64) | (Int64, Int32) => Ok(Int64),
+        (UInt8, UInt16) | (UInt16, UInt8) => Ok(UInt16),
+        (UInt8, UInt32) | (UInt32, UInt8) => Ok(UInt32),
+        (UInt8, UInt64) | (UInt64, UInt8) => Ok(UInt64),
+        (UInt16, UInt32) | (UInt32, UInt16) => Ok(UInt32),
+        (UInt16, UInt64) | (UInt64, UInt16) => Ok(UInt64),
+        (UInt32, UInt64) | (UInt64, UInt32) => Ok(UInt64),
+        (UInt8, Int16) | (Int16, UInt8) => Ok(Int16),
+        (UInt8, Int32) | (Int32, UInt8) => Ok(Int32),
+        (UInt8, Int64) | (Int64, UInt8) => Ok(Int64),
+        (UInt16, Int32) | (Int32, UInt16) => Ok(Int32),
+        (UInt16, Int64) | (Int64, UInt16) => Ok(Int64),
+        (UInt32, Int64) | (Int64, UInt32) => Ok(Int64),
+        (Float32, Float64) | (Float64, Float32) => Ok(Float64),
+        (Int8, Float32) | (Float32, Int8) => Ok(Float32),
+        (Int16, Float32) | (Float32, Int16) => Ok(Float32),
+        (Int32, Float32) | (Float32, Int32) => Ok(Float32),
+        (Int64, Float32) | (Float32, Int64) => Ok(Float64),
+        (Int8, Float64) | (Float64, Int8) => Ok(Float64),
+        (Int16, Float64) | (Float64, Int16) => Ok(Float64),
+        (Int32, Float64) | (Float64, Int32) => Ok(Float64),
+        (Int64, Float64) | (Float64, Int64) => Ok(Float64),
+        (UInt8, Float32) | (Float32, UInt8) => Ok(Float32),
+        (UInt16, Float32) | (Float32, UInt16) => Ok(Float32),
+        (UInt32, Float32) | (Float32, UInt32) => Ok(Float64),
+        (UInt64, Float32) | (Float32, UInt64) => Ok(Float64),
+        (UInt8, Float64) | (Float64, UInt8) => Ok(Float64),
+        (UInt16, Float64) | (Float64, UInt16) => Ok(Float64),
+        (UInt32, Float64) | (Float64, UInt32) => Ok(Float64),
+        (UInt64, Float64) | (Float64, UInt64) => Ok(Float64),
+        // string <-> numeric: widen to a string rather than erroring, matching
+        // how most SQL engines compare e.g. a string literal to a numeric column
+        (Utf8, _) | (_, Utf8) => Ok(Utf8),
+        _ => Err(ExecutionError::General(format!(
+            "Can't coerce types {:?} and {:?}",
+            l, r
+        ))),
+    }
+}
+
+/// Resolves `column` against `schema`, preferring an exact qualified-name
+/// match (`relation.name`, the convention `schema.field_with_name` already
+/// uses for a qualified field) and only falling back to an unqualified
+/// lookup by `name` when it's unambiguous. Once a plan can have more than one
+/// input (e.g. either side of a join), two inputs can expose a same-named
+/// column, and the unqualified fallback must refuse to silently pick one --
+/// so a name that matches more than one field is an error rather than a
+/// guess.
+pub fn resolve_column<'a>(column: &Column, schema: &'a Schema) -> Result<&'a Field> {
+    if let Some(relation) = &column.relation {
+        if let Ok(field) = schema.field_with_name(&format!("{}.{}", relation, column.name)) {
+            return Ok(field);
+        }
+    }
+    let matches: Vec<&Field> = schema
+        .fields()
+        .iter()
+        .filter(|f| unqualified_field_name(f.name()) == column.name.as_str())
+        .collect();
+    match matches.as_slice() {
+        [field] => Ok(field),
+        [] => Err(ExecutionError::General(format!(
+            "No field named '{}' found in schema",
+            column.name
+        ))),
+        _ => Err(ExecutionError::General(format!(
+            "Ambiguous column reference '{}': matches more than one field in schema",
+            column.name
+        ))),
+    }
+}
+
+/// Strips a `relation.` qualifier off a schema field's name, if it has one,
+/// so it can be compared against a bare `Column::name`.
+fn unqualified_field_name(name: &str) -> &str {
+    name.rsplit('.').next().unwrap_or(name)
+}
+
+/// Schema-aware type *and* nullability inference for `Expr`, so a rule that
+/// needs to reason about both has one trait to import instead of calling
+/// `Expr::get_type` and then guessing at nullability or defaulting it.
+pub trait ExprSchemable {
+    /// Returns the `DataType` this expression evaluates to when resolved
+    /// against `schema`.
+    fn get_type(&self, schema: &Schema) -> Result<DataType>;
+
+    /// Returns whether this expression can evaluate to `NULL` when resolved
+    /// against `schema`.
+    fn nullable(&self, schema: &Schema) -> Result<bool>;
+}
+
+impl ExprSchemable for Expr {
+    fn get_type(&self, schema: &Schema) -> Result<DataType> {
+        Expr::get_type(self, schema)
+    }
+
+    fn nullable(&self, schema: &Schema) -> Result<bool> {
+        match self {
+            Expr::Column(column) => Ok(resolve_column(column, schema)?.is_nullable()),
+            Expr::Literal(ScalarValue::Null) => Ok(true),
+            Expr::Literal(_) => Ok(false),
+            // `IS [NOT] NULL` always evaluates to a plain `true`/`false`,
+            // never to `NULL`, regardless of whether its operand can be.
+            Expr::IsNull(_) | Expr::IsNotNull(_) => Ok(false),
+            // A `CASE` with no `ELSE` branch falls through to `NULL` when no
+            // `WHEN` matches, so it's nullable even if every branch result
+            // is not.
+            Expr::Case {
+                when_then_expr,
+                else_expr,
+                ..
+            } => {
+                if else_expr.is_none() {
+                    return Ok(true);
+                }
+                for (_, then) in when_then_expr {
+                    if then.nullable(schema)? {
+                        return Ok(true);
+                    }
+                }
+                else_expr.as_ref().unwrap().nullable(schema)
+            }
+            Expr::Wildcard { .. } => Err(ExecutionError::General(
+                "Wildcard expressions are not valid in a logical query plan".to_owned(),
+            )),
+            // Everything else (casts, arithmetic/comparison/boolean
+            // operators, `BETWEEN`/`IN`, function calls, ...) is nullable
+            // iff any of its operands is, so fall back to the generic
+            // traversal helper rather than hand-matching every variant.
+            _ => {
+                for child in expr_sub_expressions(self)? {
+                    if child.nullable(schema)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+}
+
+/// Runs `optimizer` over the plan wrapped by an `Explain` node, appending the
+/// optimized plan to `stringified_plans` when `verbose` is set.
+pub fn optimize_explain(
+    optimizer: &mut dyn OptimizerRule,
+    verbose: bool,
+    plan: &LogicalPlan,
+    stringified_plans: &[StringifiedPlan],
+    schema: &Schema,
+) -> Result<LogicalPlan> {
+    let optimized_plan = optimizer.optimize(plan)?;
+    let mut stringified_plans = stringified_plans.to_vec();
+    if verbose {
+        stringified_plans.push(StringifiedPlan::new(
+            PlanType::OptimizedLogicalPlan {
+                optimizer_name: optimizer.name().to_string(),
+            },
+            format!("{:#?}", optimized_plan),
+        ));
+    }
+    Ok(LogicalPlan::Explain {
+        verbose,
+        plan: Box::new(optimized_plan),
+        stringified_plans,
+        schema: schema.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logicalplan::{col, lit};
+    use arrow::datatypes::Field;
+
+    #[test]
+    fn test_nullable_column() -> Result<()> {
+        let schema = Schema::new(vec![
+            Field::new("c0", DataType::Int64, true),
+            Field::new("c1", DataType::Int64, false),
+        ]);
+
+        assert!(col("c0").nullable(&schema)?);
+        assert!(!col("c1").nullable(&schema)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nullable_literal() -> Result<()> {
+        let schema = Schema::new(vec![]);
+
+        assert!(!lit(1_i64).nullable(&schema)?);
+        assert!(Expr::Literal(ScalarValue::Null).nullable(&schema)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nullable_cast_follows_operand() -> Result<()> {
+        let schema = Schema::new(vec![
+            Field::new("c0", DataType::Int32, true),
+            Field::new("c1", DataType::Int32, false),
+        ]);
+
+        assert!(col("c0").cast_to(&DataType::Int64, &schema)?.nullable(&schema)?);
+        assert!(!col("c1").cast_to(&DataType::Int64, &schema)?.nullable(&schema)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nullable_binary_expr_follows_either_operand() -> Result<()> {
+        let schema = Schema::new(vec![
+            Field::new("c0", DataType::Int64, true),
+            Field::new("c1", DataType::Int64, false),
+        ]);
+
+        let expr = Expr::BinaryExpr {
+            left: Box::new(col("c1")),
+            op: crate::logicalplan::Operator::Plus,
+            right: Box::new(col("c0")),
+        };
+        assert!(expr.nullable(&schema)?);
+
+        let expr = Expr::BinaryExpr {
+            left: Box::new(col("c1")),
+            op: crate::logicalplan::Operator::Plus,
+            right: Box::new(lit(1_i64)),
+        };
+        assert!(!expr.nullable(&schema)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_nullable_case_without_else_is_nullable() -> Result<()> {
+        let schema = Schema::new(vec![Field::new("c0", DataType::Boolean, true)]);
+
+        let expr = Expr::Case {
+            expr: None,
+            when_then_expr: vec![(Box::new(col("c0")), Box::new(lit(1_i64)))],
+            else_expr: None,
+        };
+        assert!(expr.nullable(&schema)?);
+
+        let expr = Expr::Case {
+            expr: None,
+            when_then_expr: vec![(Box::new(col("c0")), Box::new(lit(1_i64)))],
+            else_expr: Some(Box::new(lit(2_i64))),
+        };
+        assert!(!expr.nullable(&schema)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_input_errors_instead_of_panicking_on_arity_mismatch() {
+        assert!(single_input(&[], "Projection").is_err());
+    }
+
+    #[test]
+    fn test_schema_with_nullability_reflects_coerced_expr() -> Result<()> {
+        let resolve_schema = Schema::new(vec![Field::new("c0", DataType::Int64, true)]);
+        // the node's existing schema says this output column isn't
+        // nullable, but the rewritten expression it's paired with is --
+        // the rebuilt schema should pick that up rather than keep the
+        // stale flag.
+        let schema = Schema::new(vec![Field::new("c0", DataType::Int64, false)]);
+        let expr = vec![col("c0")];
+
+        let rebuilt = schema_with_nullability(&schema, &expr, &resolve_schema)?;
+        assert!(rebuilt.field(0).is_nullable());
+        Ok(())
+    }
+
+    #[test]
+    fn test_expect_exprs_errors_on_arity_mismatch() {
+        assert!(expect_exprs(&[lit(1_i64), lit(2_i64)], 1, "Projection").is_err());
+        assert!(expect_exprs(&[lit(1_i64)], 1, "Projection").is_ok());
+    }
+
+    #[test]
+    fn test_resolve_column_prefers_qualified_name() -> Result<()> {
+        let schema = Schema::new(vec![
+            Field::new("t1.id", DataType::Int64, true),
+            Field::new("t2.id", DataType::Int64, false),
+        ]);
+
+        let t1_id = Column {
+            relation: Some("t1".to_owned()),
+            name: "id".to_owned(),
+        };
+        assert!(resolve_column(&t1_id, &schema)?.is_nullable());
+
+        let t2_id = Column {
+            relation: Some("t2".to_owned()),
+            name: "id".to_owned(),
+        };
+        assert!(!resolve_column(&t2_id, &schema)?.is_nullable());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_column_unqualified_name_errors_when_ambiguous() {
+        let schema = Schema::new(vec![
+            Field::new("t1.id", DataType::Int64, true),
+            Field::new("t2.id", DataType::Int64, false),
+        ]);
+
+        let unqualified_id = Column {
+            relation: None,
+            name: "id".to_owned(),
+        };
+        assert!(resolve_column(&unqualified_id, &schema).is_err());
+    }
+
+    #[test]
+    fn test_resolve_column_unqualified_name_ok_when_unambiguous() -> Result<()> {
+        let schema = Schema::new(vec![Field::new("t1.id", DataType::Int64, true)]);
+
+        let unqualified_id = Column {
+            relation: None,
+            name: "id".to_owned(),
+        };
+        assert!(resolve_column(&unqualified_id, &schema)?.is_nullable());
+        Ok(())
+    }
+}